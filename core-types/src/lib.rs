@@ -19,6 +19,25 @@ pub enum Side {
 pub enum OrderType {
     Limit,
     Market,
+    // Maker-only limit: rejected if it would cross the spread
+    PostOnly,
+    // Maker-only limit: re-priced to rest just inside the spread if it would cross
+    PostOnlySlide,
+    // Floating limit pegged to an oracle reference price by a fixed offset
+    PegOffset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum TimeInForce {
+    // Good 'til cancelled: rests until filled or explicitly cancelled
+    #[default]
+    Gtc,
+    // Immediate or cancel: fill what is possible now, cancel the rest
+    Ioc,
+    // Fill or kill: fill the whole amount immediately or nothing at all
+    Fok,
+    // Good 'til date: rests until filled, cancelled, or `expiry_timestamp` passes
+    Gtd,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +51,12 @@ pub struct Order {
     pub filled_amount: Amount,
     pub price: Option<Price>,
     pub timestamp: i64,
+    pub time_in_force: TimeInForce,
+    // Absolute cutoff for GTD orders; None for orders that never expire
+    pub expiry_timestamp: Option<i64>,
+    // Max allowed adverse deviation (in bps) of a fill price from the reference
+    // price before matching is halted; None disables the slippage guard
+    pub max_slippage_bps: Option<Decimal>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1,4 +1,4 @@
-use core_types::{Amount, Order, OrderId, OrderType, Price, Side, Trade};
+use core_types::{Amount, Order, OrderId, OrderType, Price, Side, TimeInForce, Trade};
 use rust_decimal::Decimal;
 /**
  * - 2 separate b trees -> 1 bid, 1 ask
@@ -8,6 +8,57 @@ use rust_decimal::Decimal;
 use std::collections::{BTreeMap, HashMap, VecDeque};
 use uuid::Uuid;
 
+// Upper bound on GTD maker orders evicted during a single `match_order` call, so a
+// taker sweeping a level stuffed with stale orders can't be forced to do unbounded work.
+const MAX_EXPIRED_PER_MATCH: usize = 5;
+
+/// Why an order left the book, carried by `BookEvent::Out`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutReason {
+    // Order was fully filled
+    Filled,
+    // Order was cancelled (IOC/FOK remainder, post-only rejection, slippage halt, ...)
+    Cancelled,
+    // GTD order was evicted because its expiry had passed
+    Expired,
+}
+
+/// An ordered, replayable record of everything that happened during a single
+/// `match_order` call. Emitting these in occurrence order lets a settlement or
+/// market-data consumer drive state incrementally instead of diffing the snapshot.
+#[derive(Debug, Clone)]
+pub enum BookEvent {
+    // A trade between a resting maker and the incoming taker
+    Fill {
+        maker_id: OrderId,
+        taker_id: OrderId,
+        price: Price,
+        amount: Amount,
+        maker_remaining: Amount,
+        timestamp: i64,
+    },
+    // An order left the book
+    Out {
+        order_id: OrderId,
+        reason: OutReason,
+    },
+    // A taker remainder is to rest in the book at `price`; it is handed back as
+    // `remaining_taker_order` and the caller inserts it
+    Post {
+        order_id: OrderId,
+        price: Price,
+        amount: Amount,
+    },
+    // An order (maker or taker) was reduced (not traded) by self-trade prevention;
+    // no counterparty fill occurs, but the order's reserved balance must be unwound
+    // by `amount`. `remaining` is the order's unfilled amount after the reduction.
+    StpDecrement {
+        order_id: OrderId,
+        amount: Amount,
+        remaining: Amount,
+    },
+}
+
 // Result type for match_order function
 #[derive(Debug)]
 pub struct MatchResult {
@@ -20,6 +71,17 @@ pub struct MatchResult {
     pub removed_maker_orders: Vec<OrderId>,
     // Orders that were partially filled but remain in the book
     pub updated_maker_orders: Vec<Order>,
+    // True if a PostOnly taker was rejected because it would have crossed the spread
+    pub post_only_rejected: bool,
+    // Maker orders dropped because their GTD expiry had passed; balances must be unwound
+    pub expired_maker_orders: Vec<OrderId>,
+    // True if matching stopped early because a fill would have breached the taker's
+    // slippage guard, as opposed to the book simply running out of liquidity
+    pub slippage_guard_triggered: bool,
+    // Ordered event log of this match; callers may drive state from this alone
+    pub events: Vec<BookEvent>,
+    // Maker orders cancelled by self-trade prevention; removed from the book
+    pub stp_cancelled_maker_orders: Vec<OrderId>,
 }
 
 #[derive(Debug, Clone)]
@@ -34,6 +96,83 @@ pub struct OrderBookLevel {
     pub amount: Amount, // Aggregated amount at this price level
 }
 
+/// Per-market trading rules enforced on every order entering the book.
+#[derive(Debug, Clone)]
+pub struct MarketParams {
+    // Prices must be a whole multiple of this increment
+    pub tick_size: Price,
+    // Amounts must be a whole multiple of this increment
+    pub lot_size: Amount,
+    // Amounts below this threshold are rejected as dust
+    pub min_size: Amount,
+}
+
+impl Default for MarketParams {
+    fn default() -> Self {
+        MarketParams {
+            tick_size: Decimal::ONE,
+            lot_size: Decimal::ONE,
+            min_size: Decimal::ZERO,
+        }
+    }
+}
+
+/// Self-trade prevention policy applied when a taker would match a resting maker
+/// owned by the same `user_id`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StpMode {
+    // Cancel the incoming taker's remainder, leave the maker resting
+    #[default]
+    CancelTaker,
+    // Cancel the colliding maker, let the taker keep matching
+    CancelMaker,
+    // Cancel both the maker and the taker's remainder
+    CancelBoth,
+    // Reduce both by the overlapping amount and cancel whichever is smaller
+    DecrementAndCancel,
+}
+
+/// A limit order whose price floats with the oracle reference rather than being
+/// fixed. Its matchable copy lives in the `bids`/`asks` book at the current
+/// effective price; this record retains the peg parameters needed to reprice it.
+#[derive(Debug, Clone)]
+struct PeggedOrder {
+    order_id: OrderId,
+    side: Side,
+    // Added to the oracle price: negative for bids, positive for asks
+    offset: Price,
+    // Hard bound past which the effective price is clamped
+    peg_limit: Price,
+}
+
+/// Reports a pegged order whose effective price changed on the last
+/// `update_oracle_price`, so downstream risk systems can react to the re-quote.
+#[derive(Debug, Clone)]
+pub struct PeggedOrderMove {
+    pub order_id: OrderId,
+    pub old_price: Option<Price>,
+    pub new_price: Price,
+}
+
+/// Errors returned by the validating order-entry API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderBookError {
+    // Order reached the wrong side of the book
+    WrongSide,
+    // Order type not accepted by the entry point: a non-limit order to
+    // `add_bid`/`add_ask`, or a non-pegged order to `add_pegged_order`
+    NotLimitOrder,
+    // Limit order submitted without a price, or a pegged order added before any
+    // oracle price was published
+    MissingPrice,
+    // Price is not a whole multiple of the market tick size
+    InvalidTickSize { price: Price, tick_size: Price },
+    // Amount is not a whole multiple of the market lot size
+    InvalidLotSize { amount: Amount, lot_size: Amount },
+    // Amount is below the market minimum order size
+    BelowMinSize { amount: Amount, min_size: Amount },
+}
+
 pub struct OrderBook {
     // Bids ordered price highest to lowest
     bids: BTreeMap<Price, VecDeque<Order>>,
@@ -41,6 +180,20 @@ pub struct OrderBook {
     asks: BTreeMap<Price, VecDeque<Order>>,
     // Fast lookup for order price by order id
     order_locations: HashMap<OrderId, Price>,
+    // Trading rules enforced on entry; `tick_size` also drives post-only-slide repricing
+    params: MarketParams,
+    // Oracle-pegged orders keyed by their offset; repriced on `update_oracle_price`
+    pegged: BTreeMap<Price, VecDeque<PeggedOrder>>,
+    // Most recent oracle reference price, if one has been published
+    oracle_price: Option<Price>,
+    // Policy for handling a taker matching its own resting orders
+    stp_mode: StpMode,
+}
+
+impl Default for OrderBook {
+    fn default() -> Self {
+        OrderBook::new()
+    }
 }
 
 impl OrderBook {
@@ -49,38 +202,99 @@ impl OrderBook {
             bids: BTreeMap::new(),
             asks: BTreeMap::new(),
             order_locations: HashMap::new(),
+            params: MarketParams::default(),
+            pegged: BTreeMap::new(),
+            oracle_price: None,
+            stp_mode: StpMode::default(),
+        }
+    }
+
+    /// Sets the self-trade prevention policy used by `match_order`.
+    pub fn set_stp_mode(&mut self, stp_mode: StpMode) {
+        self.stp_mode = stp_mode;
+    }
+
+    pub fn with_market_params(params: MarketParams) -> Self {
+        OrderBook {
+            params,
+            ..OrderBook::new()
         }
     }
 
-    pub fn add_bid(&mut self, order: Order) {
-        // Checks
-        assert_eq!(order.side, Side::Buy);
+    /// Validates an order against this market's tick size, lot size, and minimum
+    /// size. Shared by `add_bid`/`add_ask` and the top of `match_order` so dust and
+    /// sub-tick prices can never enter the book.
+    fn validate_params(&self, order: &Order) -> Result<(), OrderBookError> {
+        let tick = self.params.tick_size;
+        if let Some(price) = order.price {
+            if tick > Decimal::ZERO && (price % tick) != Decimal::ZERO {
+                return Err(OrderBookError::InvalidTickSize {
+                    price,
+                    tick_size: tick,
+                });
+            }
+        }
+
+        if order.amount < self.params.min_size {
+            return Err(OrderBookError::BelowMinSize {
+                amount: order.amount,
+                min_size: self.params.min_size,
+            });
+        }
+
+        let lot = self.params.lot_size;
+        if lot > Decimal::ZERO && (order.amount % lot) != Decimal::ZERO {
+            return Err(OrderBookError::InvalidLotSize {
+                amount: order.amount,
+                lot_size: lot,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn add_bid(&mut self, order: Order) -> Result<(), OrderBookError> {
+        if order.side != Side::Buy {
+            return Err(OrderBookError::WrongSide);
+        }
+        if order.order_type != OrderType::Limit {
+            return Err(OrderBookError::NotLimitOrder);
+        }
 
-        assert_eq!(order.order_type, OrderType::Limit);
+        let price = order.price.ok_or(OrderBookError::MissingPrice)?;
 
-        let price = order.price.expect("Limit orders must have a price");
+        self.validate_params(&order)?;
 
         self.order_locations.insert(order.id, price);
 
         self.bids
             .entry(price)
-            .or_insert_with(VecDeque::new)
+            .or_default()
             .push_back(order);
+
+        Ok(())
     }
 
-    pub fn add_ask(&mut self, order: Order) {
-        assert_eq!(order.side, Side::Sell);
+    pub fn add_ask(&mut self, order: Order) -> Result<(), OrderBookError> {
+        if order.side != Side::Sell {
+            return Err(OrderBookError::WrongSide);
+        }
+        if order.order_type != OrderType::Limit {
+            return Err(OrderBookError::NotLimitOrder);
+        }
 
-        assert_eq!(order.order_type, OrderType::Limit);
+        let price = order.price.ok_or(OrderBookError::MissingPrice)?;
 
-        let price = order.price.expect("Limit orders must have a price");
+        self.validate_params(&order)?;
 
         self.order_locations.insert(order.id, price);
 
         self.asks
             .entry(price)
-            .or_insert_with(VecDeque::new)
+            .or_default()
             .push_back(order);
+
+        Ok(())
     }
 
     pub fn remove_bid(&mut self, order_id: OrderId) -> Option<Order> {
@@ -185,14 +399,302 @@ impl OrderBook {
         ))
     }
 
+    /// Whether a prospective fill at `price` deviates adversely from `reference` by
+    /// more than `max_bps`. "Adverse" means paying up for a buy or selling down for a
+    /// sell; favourable deviations never trip the guard.
+    fn breaches_slippage(side: Side, price: Price, reference: Price, max_bps: Decimal) -> bool {
+        if reference <= Decimal::ZERO {
+            return false;
+        }
+        let bps_scale = Decimal::from(10_000);
+        let deviation_bps = match side {
+            Side::Buy => (price - reference) / reference * bps_scale,
+            Side::Sell => (reference - price) / reference * bps_scale,
+        };
+        deviation_bps > max_bps
+    }
+
+    /// Computes a pegged order's effective price from `oracle_price`, clamping it at
+    /// `peg_limit`: a bid never rests above its ceiling, an ask never below its floor.
+    fn peg_effective_price(side: Side, oracle_price: Price, offset: Price, peg_limit: Price) -> Price {
+        let raw = oracle_price + offset;
+        match side {
+            Side::Buy => raw.min(peg_limit),
+            Side::Sell => raw.max(peg_limit),
+        }
+    }
+
+    /// Snaps `price` onto this market's tick grid so a pegged order always rests
+    /// at a valid level: a bid rounds down, an ask rounds up, so neither becomes
+    /// more aggressive than its raw peg target.
+    fn snap_to_tick(&self, side: Side, price: Price) -> Price {
+        let tick = self.params.tick_size;
+        if tick <= Decimal::ZERO {
+            return price;
+        }
+        let floored = (price / tick).floor() * tick;
+        match side {
+            Side::Buy => floored,
+            Side::Sell if floored == price => price,
+            Side::Sell => floored + tick,
+        }
+    }
+
+    /// Holds a pegged order's effective price on the passive side of the spread:
+    /// a repriced bid is capped one tick below the best ask and an ask one tick
+    /// above the best bid, so re-quoting an order can never lock or cross the book.
+    fn clamp_non_crossing(&self, side: Side, price: Price) -> Price {
+        match side {
+            Side::Buy => match self.get_best_ask() {
+                Some(best_ask) if price >= best_ask => best_ask - self.params.tick_size,
+                _ => price,
+            },
+            Side::Sell => match self.get_best_bid() {
+                Some(best_bid) if price <= best_bid => best_bid + self.params.tick_size,
+                _ => price,
+            },
+        }
+    }
+
+    /// Adds an oracle-pegged floating limit order. The order rests in the book at its
+    /// current effective price and is re-bucketed whenever the oracle price changes.
+    /// `offset` should be negative for bids and positive for asks; `peg_limit` caps how
+    /// far the order may float. Requires an oracle price to have been published.
+    pub fn add_pegged_order(
+        &mut self,
+        mut order: Order,
+        offset: Price,
+        peg_limit: Price,
+    ) -> Result<(), OrderBookError> {
+        if order.order_type != OrderType::PegOffset {
+            return Err(OrderBookError::NotLimitOrder);
+        }
+        let oracle_price = self.oracle_price.ok_or(OrderBookError::MissingPrice)?;
+
+        let effective = self.snap_to_tick(
+            order.side,
+            Self::peg_effective_price(order.side, oracle_price, offset, peg_limit),
+        );
+        order.price = Some(effective);
+
+        // Pegged orders must clear the same tick/lot/min-size rules as any resting
+        // limit; the effective price is snapped to the tick grid above so only dust
+        // and odd-lot amounts can still be rejected here.
+        self.validate_params(&order)?;
+
+        // Never enter crossing the book: hold the effective price one tick inside
+        // the opposing best if the peg would otherwise lock or cross.
+        let effective = self.clamp_non_crossing(order.side, effective);
+        order.price = Some(effective);
+
+        let order_id = order.id;
+        let side = order.side;
+
+        self.order_locations.insert(order_id, effective);
+        let book = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        book.entry(effective)
+            .or_default()
+            .push_back(order);
+
+        self.pegged
+            .entry(offset)
+            .or_default()
+            .push_back(PeggedOrder {
+                order_id,
+                side,
+                offset,
+                peg_limit,
+            });
+
+        Ok(())
+    }
+
+    /// Publishes a new oracle price and reprices every pegged order, re-bucketing each
+    /// into the correct `bids`/`asks` level and clamping at its `peg_limit`. Returns the
+    /// orders whose effective price moved. Pegged records whose book copy has since been
+    /// filled or cancelled are pruned here.
+    pub fn update_oracle_price(&mut self, price: Price) -> Vec<PeggedOrderMove> {
+        self.oracle_price = Some(price);
+
+        // Snapshot so the book can be mutated while we walk the pegged set.
+        let entries: Vec<(OrderId, Side, Price, Price)> = self
+            .pegged
+            .values()
+            .flatten()
+            .map(|p| (p.order_id, p.side, p.offset, p.peg_limit))
+            .collect();
+
+        let mut moves = Vec::new();
+        let mut stale = Vec::new();
+
+        for (id, side, offset, peg_limit) in entries {
+            let old_price = match self.order_locations.get(&id) {
+                Some(p) => *p,
+                None => {
+                    // Book copy is gone (filled/cancelled); drop the peg record.
+                    stale.push((offset, id));
+                    continue;
+                }
+            };
+
+            let effective = self.snap_to_tick(
+                side,
+                Self::peg_effective_price(side, price, offset, peg_limit),
+            );
+            // Hold the re-quote passive: a reprice that would cross the book rests
+            // one tick inside the opposing best instead of locking it.
+            let effective = self.clamp_non_crossing(side, effective);
+            if effective == old_price {
+                continue;
+            }
+
+            if let Some(mut order) = self.remove_order(id, side) {
+                order.price = Some(effective);
+                self.order_locations.insert(id, effective);
+                let book = match side {
+                    Side::Buy => &mut self.bids,
+                    Side::Sell => &mut self.asks,
+                };
+                book.entry(effective)
+                    .or_default()
+                    .push_back(order);
+
+                moves.push(PeggedOrderMove {
+                    order_id: id,
+                    old_price: Some(old_price),
+                    new_price: effective,
+                });
+            } else {
+                stale.push((offset, id));
+            }
+        }
+
+        for (offset, id) in stale {
+            if let Some(orders) = self.pegged.get_mut(&offset) {
+                orders.retain(|p| p.order_id != id);
+                if orders.is_empty() {
+                    self.pegged.remove(&offset);
+                }
+            }
+        }
+
+        moves
+    }
+
+    /// Whether `taker_order` could be filled in full right now against the resting
+    /// book, honoring its limit price and skipping GTD makers that have already
+    /// expired. When `reference_price` is supplied, levels beyond the taker's
+    /// slippage bound are excluded so fill-or-kill stays consistent with the guard
+    /// that `match_order_with_guard` enforces. Self-owned makers never fill the taker:
+    /// under `StpMode::CancelMaker` they are skipped, and under every other mode the
+    /// first one reached makes the order unfillable, since matching would stop or
+    /// decrement the taker there. Used to decide fill-or-kill before any state is
+    /// mutated.
+    fn is_fully_fillable(&self, taker_order: &Order, reference_price: Option<Price>) -> bool {
+        let mut needed = taker_order.amount - taker_order.filled_amount;
+        if needed <= Decimal::ZERO {
+            return true;
+        }
+
+        let guard_bps = reference_price.and(taker_order.max_slippage_bps);
+
+        let (levels, limit): (Vec<(&Price, &VecDeque<Order>)>, Option<Price>) =
+            match taker_order.side {
+                Side::Buy => (self.asks.iter().collect(), taker_order.price),
+                Side::Sell => (self.bids.iter().rev().collect(), taker_order.price),
+            };
+
+        for (price, orders_at_price) in levels {
+            match taker_order.order_type {
+                OrderType::Limit => {
+                    let limit = limit.expect("Limit orders must have a price");
+                    let crossed = match taker_order.side {
+                        Side::Buy => *price <= limit,
+                        Side::Sell => *price >= limit,
+                    };
+                    if !crossed {
+                        break;
+                    }
+                }
+                OrderType::Market => {}
+                OrderType::PostOnly | OrderType::PostOnlySlide | OrderType::PegOffset => return false,
+            }
+
+            // A level the slippage guard would halt on cannot contribute to a fill.
+            if let Some(max_bps) = guard_bps {
+                if Self::breaches_slippage(taker_order.side, *price, reference_price.unwrap(), max_bps)
+                {
+                    break;
+                }
+            }
+
+            for maker_order in orders_at_price {
+                if let Some(expiry) = maker_order.expiry_timestamp {
+                    if expiry < taker_order.timestamp {
+                        continue; // expired makers cannot contribute liquidity
+                    }
+                }
+                if maker_order.user_id == taker_order.user_id {
+                    match self.stp_mode {
+                        // Matching skips the cancelled maker and keeps going
+                        StpMode::CancelMaker => continue,
+                        // Matching cancels or decrements the taker here without a fill
+                        _ => return false,
+                    }
+                }
+                needed -= maker_order.amount - maker_order.filled_amount;
+                if needed <= Decimal::ZERO {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
     /// For market orders --> execute the order immediately
     /// Matches an incoming (taker) order against the existing orders (maker) in the book.
     /// Handles both Limit and Market orders.
-    pub fn match_order(&mut self, mut taker_order: Order) -> MatchResult {
+    pub fn match_order(&mut self, taker_order: Order) -> MatchResult {
+        self.match_order_inner(taker_order, None)
+    }
+
+    /// Like `match_order`, but enforces the taker's `max_slippage_bps` against
+    /// `reference_price`: before each fill the maker price is compared to the
+    /// reference, and if it deviates adversely by more than the allowed bps,
+    /// matching halts and the unfilled remainder is cancelled rather than crossing.
+    /// `MatchResult::slippage_guard_triggered` distinguishes this from a dry book.
+    pub fn match_order_with_guard(
+        &mut self,
+        taker_order: Order,
+        reference_price: Price,
+    ) -> MatchResult {
+        self.match_order_inner(taker_order, Some(reference_price))
+    }
+
+    fn match_order_inner(
+        &mut self,
+        mut taker_order: Order,
+        reference_price: Option<Price>,
+    ) -> MatchResult {
         let mut trades = Vec::new();
         let mut removed_maker_orders = Vec::new();
         let mut updated_maker_orders = Vec::new();
+        let mut expired_maker_orders = Vec::new();
+        let mut expired_dropped = 0usize;
         let mut remaining_taker_order = None;
+        let mut events: Vec<BookEvent> = Vec::new();
+        let mut stp_cancelled_maker_orders = Vec::new();
+        // Set when self-trade prevention cancels the taker's remainder.
+        let mut stp_cancel_taker = false;
+        let stp_mode = self.stp_mode;
+        // Set when a prospective fill breaches the slippage guard, halting matching.
+        let mut slippage_halted = false;
+        // Active guard bps, only when both a reference price and a bound are present.
+        let guard_bps = reference_price.and(taker_order.max_slippage_bps);
 
         let taker_unfilled_amount = taker_order.amount - taker_order.filled_amount;
 
@@ -203,6 +705,152 @@ impl OrderBook {
                 remaining_taker_order: Some(taker_order), // Return original order state
                 removed_maker_orders,
                 updated_maker_orders,
+                post_only_rejected: false,
+                expired_maker_orders,
+                slippage_guard_triggered: false,
+                events,
+                stp_cancelled_maker_orders,
+            };
+        }
+
+        // Reject dust and sub-tick takers before touching the book; a rejected taker
+        // is handed straight back unfilled so the caller can unwind its reservation.
+        if self.validate_params(&taker_order).is_err() {
+            events.push(BookEvent::Out {
+                order_id: taker_order.id,
+                reason: OutReason::Cancelled,
+            });
+            return MatchResult {
+                trades,
+                remaining_taker_order: Some(taker_order),
+                removed_maker_orders,
+                updated_maker_orders,
+                post_only_rejected: false,
+                expired_maker_orders,
+                slippage_guard_triggered: false,
+                events,
+                stp_cancelled_maker_orders,
+            };
+        }
+
+        // Fill-or-kill: the entire amount must be immediately fillable at acceptable
+        // prices, otherwise the order is killed without producing a single trade.
+        if taker_order.time_in_force == TimeInForce::Fok
+            && !self.is_fully_fillable(&taker_order, reference_price)
+        {
+            events.push(BookEvent::Out {
+                order_id: taker_order.id,
+                reason: OutReason::Cancelled,
+            });
+            return MatchResult {
+                trades,
+                remaining_taker_order: None,
+                removed_maker_orders,
+                updated_maker_orders,
+                post_only_rejected: false,
+                expired_maker_orders,
+                slippage_guard_triggered: false,
+                events,
+                stp_cancelled_maker_orders,
+            };
+        }
+
+        // Post-only orders never take liquidity: if they would cross the spread we
+        // either reject them outright (PostOnly) or slide them to rest just inside
+        // the spread (PostOnlySlide). Unlike the request's "add it to the book"
+        // wording, a slid order is not inserted here: it is returned as the remainder
+        // at its resting price (with a `Post` event), matching the resting-limit
+        // contract so callers stay the single owner of insertion and balances.
+        if matches!(
+            taker_order.order_type,
+            OrderType::PostOnly | OrderType::PostOnlySlide
+        ) {
+            let limit_price = taker_order
+                .price
+                .expect("Post-only orders must have a price");
+
+            let would_cross = match taker_order.side {
+                Side::Buy => self
+                    .get_best_ask()
+                    .is_some_and(|best_ask| limit_price >= best_ask),
+                Side::Sell => self
+                    .get_best_bid()
+                    .is_some_and(|best_bid| limit_price <= best_bid),
+            };
+
+            if would_cross {
+                match taker_order.order_type {
+                    OrderType::PostOnly => {
+                        events.push(BookEvent::Out {
+                            order_id: taker_order.id,
+                            reason: OutReason::Cancelled,
+                        });
+                        return MatchResult {
+                            trades,
+                            remaining_taker_order: None,
+                            removed_maker_orders,
+                            updated_maker_orders,
+                            post_only_rejected: true,
+                            expired_maker_orders,
+                            slippage_guard_triggered: false,
+                            events,
+                            stp_cancelled_maker_orders,
+                        };
+                    }
+                    OrderType::PostOnlySlide => {
+                        let slid_price = match taker_order.side {
+                            // Safe: would_cross implies the opposing best exists
+                            Side::Buy => limit_price
+                                .min(self.get_best_ask().unwrap() - self.params.tick_size),
+                            Side::Sell => limit_price
+                                .max(self.get_best_bid().unwrap() + self.params.tick_size),
+                        };
+                        taker_order.price = Some(slid_price);
+                    }
+                    _ => unreachable!(),
+                }
+            }
+
+            // Mirror the resting-limit contract: report where the order should rest
+            // and hand it back as the remainder, but leave the actual insertion to
+            // the caller so post-only orders aren't double-added to the book.
+            let rest_price = taker_order.price.unwrap();
+            events.push(BookEvent::Post {
+                order_id: taker_order.id,
+                price: rest_price,
+                amount: taker_order.amount - taker_order.filled_amount,
+            });
+            return MatchResult {
+                trades,
+                remaining_taker_order: Some(taker_order),
+                removed_maker_orders,
+                updated_maker_orders,
+                post_only_rejected: false,
+                expired_maker_orders,
+                slippage_guard_triggered: false,
+                events,
+                stp_cancelled_maker_orders,
+            };
+        }
+
+        // Oracle-pegged orders are entered and repriced through `add_pegged_order`;
+        // they are never matched as takers. Reject rather than panicking in the
+        // side loops if one reaches this public entry point.
+        if taker_order.order_type == OrderType::PegOffset {
+            events.push(BookEvent::Out {
+                order_id: taker_order.id,
+                reason: OutReason::Cancelled,
+            });
+            return MatchResult {
+                trades,
+                remaining_taker_order: None,
+                removed_maker_orders,
+                updated_maker_orders,
+                post_only_rejected: false,
+                expired_maker_orders,
+                slippage_guard_triggered: false,
+                events,
+                stp_cancelled_maker_orders,
             };
         }
 
@@ -223,6 +871,7 @@ impl OrderBook {
                         OrderType::Market => {
                             // Market order takes any price
                         }
+                        OrderType::PostOnly | OrderType::PostOnlySlide | OrderType::PegOffset => unreachable!(),
                     }
 
                     if let Some(orders_at_price) = self.asks.get_mut(&price) {
@@ -234,6 +883,24 @@ impl OrderBook {
                                 break; // Taker order fully filled
                             }
 
+                            // GTD: lazily evict makers whose expiry has passed rather
+                            // than trading against them, capped per call to bound work.
+                            if let Some(expiry) = maker_order.expiry_timestamp {
+                                if expiry < taker_order.timestamp {
+                                    if expired_dropped < MAX_EXPIRED_PER_MATCH {
+                                        orders_fully_filled.push(index);
+                                        expired_maker_orders.push(maker_order.id);
+                                        self.order_locations.remove(&maker_order.id);
+                                        expired_dropped += 1;
+                                        events.push(BookEvent::Out {
+                                            order_id: maker_order.id,
+                                            reason: OutReason::Expired,
+                                        });
+                                    }
+                                    continue;
+                                }
+                            }
+
                             let maker_remaining = maker_order.amount - maker_order.filled_amount;
                             let fill_amount = taker_remaining.min(maker_remaining);
 
@@ -241,11 +908,97 @@ impl OrderBook {
                                 continue; // Maker order already filled? Skip.
                             }
 
+                            // Self-trade prevention: never trade a user against itself.
+                            if maker_order.user_id == taker_order.user_id {
+                                match stp_mode {
+                                    StpMode::CancelTaker => {
+                                        stp_cancel_taker = true;
+                                        break;
+                                    }
+                                    StpMode::CancelMaker => {
+                                        orders_fully_filled.push(index);
+                                        stp_cancelled_maker_orders.push(maker_order.id);
+                                        self.order_locations.remove(&maker_order.id);
+                                        events.push(BookEvent::Out {
+                                            order_id: maker_order.id,
+                                            reason: OutReason::Cancelled,
+                                        });
+                                        continue;
+                                    }
+                                    StpMode::CancelBoth => {
+                                        orders_fully_filled.push(index);
+                                        stp_cancelled_maker_orders.push(maker_order.id);
+                                        self.order_locations.remove(&maker_order.id);
+                                        events.push(BookEvent::Out {
+                                            order_id: maker_order.id,
+                                            reason: OutReason::Cancelled,
+                                        });
+                                        stp_cancel_taker = true;
+                                        break;
+                                    }
+                                    StpMode::DecrementAndCancel => {
+                                        let overlap = fill_amount;
+                                        taker_order.filled_amount += overlap;
+                                        maker_order.filled_amount += overlap;
+                                        // The overlap is consumed from the taker with no
+                                        // trade, so record a symmetric reduction; its
+                                        // reserved balance must be unwound just like the
+                                        // maker's.
+                                        events.push(BookEvent::StpDecrement {
+                                            order_id: taker_order.id,
+                                            amount: overlap,
+                                            remaining: taker_order.amount
+                                                - taker_order.filled_amount,
+                                        });
+                                        let maker_exhausted = maker_remaining <= taker_remaining;
+                                        let taker_exhausted = taker_remaining <= maker_remaining;
+                                        if maker_exhausted {
+                                            orders_fully_filled.push(index);
+                                            stp_cancelled_maker_orders.push(maker_order.id);
+                                            self.order_locations.remove(&maker_order.id);
+                                            events.push(BookEvent::Out {
+                                                order_id: maker_order.id,
+                                                reason: OutReason::Cancelled,
+                                            });
+                                        } else {
+                                            // Maker survives, decremented by the overlap:
+                                            // record the reduction so its balance unwinds.
+                                            events.push(BookEvent::StpDecrement {
+                                                order_id: maker_order.id,
+                                                amount: overlap,
+                                                remaining: maker_order.amount
+                                                    - maker_order.filled_amount,
+                                            });
+                                            updated_maker_orders.push(maker_order.clone());
+                                        }
+                                        if taker_exhausted {
+                                            stp_cancel_taker = true;
+                                            break;
+                                        }
+                                        continue;
+                                    }
+                                }
+                            }
+
+                            // Stop before crossing past the taker's slippage tolerance.
+                            if let Some(max_bps) = guard_bps {
+                                if Self::breaches_slippage(
+                                    Side::Buy,
+                                    price,
+                                    reference_price.unwrap(),
+                                    max_bps,
+                                ) {
+                                    slippage_halted = true;
+                                    break;
+                                }
+                            }
+
                             // Update filled amounts
                             taker_order.filled_amount += fill_amount;
                             maker_order.filled_amount += fill_amount;
 
                             // Create Trade
+                            let timestamp = chrono::Utc::now().timestamp_millis();
                             let trade = Trade {
                                 id: Uuid::new_v4(), // Generate unique trade ID
                                 market_id: taker_order.market_id.clone(),
@@ -253,16 +1006,30 @@ impl OrderBook {
                                 maker_order_id: maker_order.id,
                                 amount: fill_amount,
                                 price, // Trade occurs at the maker's price
-                                timestamp: chrono::Utc::now().timestamp_millis(),
+                                timestamp,
                                 taker_side: Side::Buy,
                             };
                             trades.push(trade);
 
+                            let maker_remaining = maker_order.amount - maker_order.filled_amount;
+                            events.push(BookEvent::Fill {
+                                maker_id: maker_order.id,
+                                taker_id: taker_order.id,
+                                price,
+                                amount: fill_amount,
+                                maker_remaining,
+                                timestamp,
+                            });
+
                             // Check if maker order is fully filled
                             if maker_order.filled_amount >= maker_order.amount {
                                 orders_fully_filled.push(index);
                                 removed_maker_orders.push(maker_order.id);
                                 self.order_locations.remove(&maker_order.id);
+                                events.push(BookEvent::Out {
+                                    order_id: maker_order.id,
+                                    reason: OutReason::Filled,
+                                });
                             } else {
                                 // Add to updated list if partially filled
                                 updated_maker_orders.push(maker_order.clone());
@@ -280,6 +1047,10 @@ impl OrderBook {
                         }
                     } // End if let Some(orders_at_price)
 
+                    if slippage_halted || stp_cancel_taker {
+                        break; // Slippage guard tripped or taker cancelled by STP
+                    }
+
                     if taker_order.filled_amount >= taker_order.amount {
                         break; // Taker order fully filled
                     }
@@ -303,6 +1074,7 @@ impl OrderBook {
                         OrderType::Market => {
                             // Market order takes any price
                         }
+                        OrderType::PostOnly | OrderType::PostOnlySlide | OrderType::PegOffset => unreachable!(),
                     }
 
                     if let Some(orders_at_price) = self.bids.get_mut(&price) {
@@ -314,6 +1086,24 @@ impl OrderBook {
                                 break; // Taker order fully filled
                             }
 
+                            // GTD: lazily evict makers whose expiry has passed rather
+                            // than trading against them, capped per call to bound work.
+                            if let Some(expiry) = maker_order.expiry_timestamp {
+                                if expiry < taker_order.timestamp {
+                                    if expired_dropped < MAX_EXPIRED_PER_MATCH {
+                                        orders_fully_filled.push(index);
+                                        expired_maker_orders.push(maker_order.id);
+                                        self.order_locations.remove(&maker_order.id);
+                                        expired_dropped += 1;
+                                        events.push(BookEvent::Out {
+                                            order_id: maker_order.id,
+                                            reason: OutReason::Expired,
+                                        });
+                                    }
+                                    continue;
+                                }
+                            }
+
                             let maker_remaining = maker_order.amount - maker_order.filled_amount;
                             let fill_amount = taker_remaining.min(maker_remaining);
 
@@ -321,11 +1111,97 @@ impl OrderBook {
                                 continue;
                             }
 
+                            // Self-trade prevention: never trade a user against itself.
+                            if maker_order.user_id == taker_order.user_id {
+                                match stp_mode {
+                                    StpMode::CancelTaker => {
+                                        stp_cancel_taker = true;
+                                        break;
+                                    }
+                                    StpMode::CancelMaker => {
+                                        orders_fully_filled.push(index);
+                                        stp_cancelled_maker_orders.push(maker_order.id);
+                                        self.order_locations.remove(&maker_order.id);
+                                        events.push(BookEvent::Out {
+                                            order_id: maker_order.id,
+                                            reason: OutReason::Cancelled,
+                                        });
+                                        continue;
+                                    }
+                                    StpMode::CancelBoth => {
+                                        orders_fully_filled.push(index);
+                                        stp_cancelled_maker_orders.push(maker_order.id);
+                                        self.order_locations.remove(&maker_order.id);
+                                        events.push(BookEvent::Out {
+                                            order_id: maker_order.id,
+                                            reason: OutReason::Cancelled,
+                                        });
+                                        stp_cancel_taker = true;
+                                        break;
+                                    }
+                                    StpMode::DecrementAndCancel => {
+                                        let overlap = fill_amount;
+                                        taker_order.filled_amount += overlap;
+                                        maker_order.filled_amount += overlap;
+                                        // The overlap is consumed from the taker with no
+                                        // trade, so record a symmetric reduction; its
+                                        // reserved balance must be unwound just like the
+                                        // maker's.
+                                        events.push(BookEvent::StpDecrement {
+                                            order_id: taker_order.id,
+                                            amount: overlap,
+                                            remaining: taker_order.amount
+                                                - taker_order.filled_amount,
+                                        });
+                                        let maker_exhausted = maker_remaining <= taker_remaining;
+                                        let taker_exhausted = taker_remaining <= maker_remaining;
+                                        if maker_exhausted {
+                                            orders_fully_filled.push(index);
+                                            stp_cancelled_maker_orders.push(maker_order.id);
+                                            self.order_locations.remove(&maker_order.id);
+                                            events.push(BookEvent::Out {
+                                                order_id: maker_order.id,
+                                                reason: OutReason::Cancelled,
+                                            });
+                                        } else {
+                                            // Maker survives, decremented by the overlap:
+                                            // record the reduction so its balance unwinds.
+                                            events.push(BookEvent::StpDecrement {
+                                                order_id: maker_order.id,
+                                                amount: overlap,
+                                                remaining: maker_order.amount
+                                                    - maker_order.filled_amount,
+                                            });
+                                            updated_maker_orders.push(maker_order.clone());
+                                        }
+                                        if taker_exhausted {
+                                            stp_cancel_taker = true;
+                                            break;
+                                        }
+                                        continue;
+                                    }
+                                }
+                            }
+
+                            // Stop before crossing past the taker's slippage tolerance.
+                            if let Some(max_bps) = guard_bps {
+                                if Self::breaches_slippage(
+                                    Side::Sell,
+                                    price,
+                                    reference_price.unwrap(),
+                                    max_bps,
+                                ) {
+                                    slippage_halted = true;
+                                    break;
+                                }
+                            }
+
                             // Update filled amounts
                             taker_order.filled_amount += fill_amount;
                             maker_order.filled_amount += fill_amount;
 
                             // Create Trade
+                            let timestamp = chrono::Utc::now().timestamp_millis();
                             let trade = Trade {
                                 id: Uuid::new_v4(),
                                 market_id: taker_order.market_id.clone(),
@@ -333,16 +1209,30 @@ impl OrderBook {
                                 maker_order_id: maker_order.id,
                                 amount: fill_amount,
                                 price, // Trade occurs at the maker's price
-                                timestamp: chrono::Utc::now().timestamp_millis(),
+                                timestamp,
                                 taker_side: Side::Sell,
                             };
                             trades.push(trade);
 
+                            let maker_remaining = maker_order.amount - maker_order.filled_amount;
+                            events.push(BookEvent::Fill {
+                                maker_id: maker_order.id,
+                                taker_id: taker_order.id,
+                                price,
+                                amount: fill_amount,
+                                maker_remaining,
+                                timestamp,
+                            });
+
                             // Check if maker order is fully filled
                             if maker_order.filled_amount >= maker_order.amount {
                                 orders_fully_filled.push(index);
                                 removed_maker_orders.push(maker_order.id);
                                 self.order_locations.remove(&maker_order.id);
+                                events.push(BookEvent::Out {
+                                    order_id: maker_order.id,
+                                    reason: OutReason::Filled,
+                                });
                             } else {
                                 updated_maker_orders.push(maker_order.clone());
                             }
@@ -359,6 +1249,10 @@ impl OrderBook {
                         }
                     } // End if let Some(orders_at_price)
 
+                    if slippage_halted || stp_cancel_taker {
+                        break; // Slippage guard tripped or taker cancelled by STP
+                    }
+
                     if taker_order.filled_amount >= taker_order.amount {
                         break; // Taker order fully filled
                     }
@@ -366,17 +1260,39 @@ impl OrderBook {
             }
         }
 
-        // If taker order is a Limit order and not fully filled, store the remainder
-        if taker_order.order_type == OrderType::Limit
-            && taker_order.filled_amount < taker_order.amount
+        // Classify the taker's final state, emitting the matching terminal event.
+        // An STP-cancelled taker is cancelled even when fully decremented; IOC/market
+        // and slippage-halted remainders are cancelled (a `None` remainder); an
+        // unfilled resting Limit remainder gets a `Post` event and is handed back for
+        // the caller to rest.
+        let taker_id = taker_order.id;
+        let taker_remaining = taker_order.amount - taker_order.filled_amount;
+        if stp_cancel_taker {
+            events.push(BookEvent::Out {
+                order_id: taker_id,
+                reason: OutReason::Cancelled,
+            });
+        } else if taker_remaining <= Decimal::ZERO {
+            events.push(BookEvent::Out {
+                order_id: taker_id,
+                reason: OutReason::Filled,
+            });
+        } else if taker_order.order_type == OrderType::Limit
+            && taker_order.time_in_force != TimeInForce::Ioc
+            && !slippage_halted
         {
+            events.push(BookEvent::Post {
+                order_id: taker_id,
+                price: taker_order.price.unwrap(),
+                amount: taker_remaining,
+            });
             remaining_taker_order = Some(taker_order);
-        } else if taker_order.order_type == OrderType::Market
-            && taker_order.filled_amount < taker_order.amount
-        {
-            // Market order couldn't be fully filled, it just expires partially filled
-            // We don't set remaining_taker_order, signaling it's done.
-            // The caller can inspect taker_order.filled_amount if needed.
+        } else {
+            // Market/IOC/slippage remainder: cancelled rather than rested.
+            events.push(BookEvent::Out {
+                order_id: taker_id,
+                reason: OutReason::Cancelled,
+            });
         }
 
         MatchResult {
@@ -384,6 +1300,11 @@ impl OrderBook {
             remaining_taker_order,
             removed_maker_orders,
             updated_maker_orders,
+            post_only_rejected: false,
+            expired_maker_orders,
+            slippage_guard_triggered: slippage_halted,
+            events,
+            stp_cancelled_maker_orders,
         }
     }
 
@@ -448,3 +1369,449 @@ impl OrderBook {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core_types::UserId;
+
+    fn order(
+        side: Side,
+        order_type: OrderType,
+        price: Option<i64>,
+        amount: i64,
+        user: UserId,
+    ) -> Order {
+        Order {
+            id: Uuid::new_v4(),
+            user_id: user,
+            market_id: "BTC-USD".to_string(),
+            side,
+            order_type,
+            amount: Decimal::from(amount),
+            filled_amount: Decimal::ZERO,
+            price: price.map(Decimal::from),
+            timestamp: 0,
+            time_in_force: TimeInForce::Gtc,
+            expiry_timestamp: None,
+            max_slippage_bps: None,
+        }
+    }
+
+    #[test]
+    fn post_only_rejected_when_it_would_cross() {
+        let mut book = OrderBook::new();
+        book.add_ask(order(Side::Sell, OrderType::Limit, Some(100), 5, Uuid::new_v4()))
+            .unwrap();
+
+        let taker = order(Side::Buy, OrderType::PostOnly, Some(100), 5, Uuid::new_v4());
+        let taker_id = taker.id;
+        let result = book.match_order(taker);
+
+        assert!(result.post_only_rejected);
+        assert!(result.trades.is_empty());
+        assert!(result.remaining_taker_order.is_none());
+        // Rejected order must never enter the book.
+        assert!(book.get_order_by_id(&taker_id).is_none());
+    }
+
+    #[test]
+    fn post_only_slide_reprices_just_inside_the_spread() {
+        let mut book = OrderBook::new();
+        book.add_ask(order(Side::Sell, OrderType::Limit, Some(100), 5, Uuid::new_v4()))
+            .unwrap();
+
+        let taker = order(Side::Buy, OrderType::PostOnlySlide, Some(105), 5, Uuid::new_v4());
+        let taker_id = taker.id;
+        let result = book.match_order(taker);
+
+        let remainder = result.remaining_taker_order.expect("slid order returned");
+        // best_ask (100) - one tick (1)
+        assert_eq!(remainder.price, Some(Decimal::from(99)));
+        assert!(result.trades.is_empty());
+        // match_order must not self-rest; the caller owns insertion.
+        assert!(book.get_order_by_id(&taker_id).is_none());
+    }
+
+    #[test]
+    fn post_only_rests_and_is_not_double_inserted() {
+        let mut book = OrderBook::new();
+        book.add_ask(order(Side::Sell, OrderType::Limit, Some(100), 5, Uuid::new_v4()))
+            .unwrap();
+
+        let taker = order(Side::Buy, OrderType::PostOnly, Some(90), 5, Uuid::new_v4());
+        let taker_id = taker.id;
+        let result = book.match_order(taker);
+
+        // Does not cross, so it comes back as a resting remainder the caller adds.
+        assert!(!result.post_only_rejected);
+        let remainder = result.remaining_taker_order.expect("resting remainder");
+        assert_eq!(remainder.price, Some(Decimal::from(90)));
+        assert!(book.get_order_by_id(&taker_id).is_none());
+        assert!(matches!(
+            result.events.last(),
+            Some(BookEvent::Post { price, .. }) if *price == Decimal::from(90)
+        ));
+    }
+
+    #[test]
+    fn pegged_order_taker_is_rejected_not_panicking() {
+        let mut book = OrderBook::new();
+        let taker = order(Side::Buy, OrderType::PegOffset, Some(100), 5, Uuid::new_v4());
+        let result = book.match_order(taker);
+
+        assert!(result.trades.is_empty());
+        assert!(result.remaining_taker_order.is_none());
+        assert!(matches!(
+            result.events.last(),
+            Some(BookEvent::Out {
+                reason: OutReason::Cancelled,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn update_oracle_price_rebuckets_pegged_orders() {
+        let mut book = OrderBook::new();
+        book.update_oracle_price(Decimal::from(100));
+
+        let bid = order(Side::Buy, OrderType::PegOffset, None, 5, Uuid::new_v4());
+        let bid_id = bid.id;
+        // Rest at oracle - 2 = 98, with a ceiling far above so it doesn't clamp.
+        book.add_pegged_order(bid, Decimal::from(-2), Decimal::from(1_000))
+            .unwrap();
+        assert_eq!(book.get_best_bid(), Some(Decimal::from(98)));
+
+        // Oracle moves to 110 -> effective 108, re-bucketed to the new level.
+        let moves = book.update_oracle_price(Decimal::from(110));
+        assert_eq!(book.get_best_bid(), Some(Decimal::from(108)));
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].order_id, bid_id);
+        assert_eq!(moves[0].new_price, Decimal::from(108));
+    }
+
+    #[test]
+    fn update_oracle_price_clamps_at_peg_limit() {
+        let mut book = OrderBook::new();
+        book.update_oracle_price(Decimal::from(100));
+
+        let bid = order(Side::Buy, OrderType::PegOffset, None, 5, Uuid::new_v4());
+        // Ceiling of 105: once oracle + offset would exceed it, the order rests at 105.
+        book.add_pegged_order(bid, Decimal::from(-2), Decimal::from(105))
+            .unwrap();
+        assert_eq!(book.get_best_bid(), Some(Decimal::from(98)));
+
+        book.update_oracle_price(Decimal::from(200));
+        assert_eq!(book.get_best_bid(), Some(Decimal::from(105)));
+    }
+
+    #[test]
+    fn pegged_reprice_that_would_cross_is_held_inside_the_spread() {
+        let mut book = OrderBook::new();
+        book.update_oracle_price(Decimal::from(90));
+        // A resting ask from another participant sets the opposing best.
+        book.add_ask(order(Side::Sell, OrderType::Limit, Some(100), 5, Uuid::new_v4()))
+            .unwrap();
+
+        let bid = order(Side::Buy, OrderType::PegOffset, None, 5, Uuid::new_v4());
+        book.add_pegged_order(bid, Decimal::ZERO, Decimal::from(1_000))
+            .unwrap();
+        assert_eq!(book.get_best_bid(), Some(Decimal::from(90)));
+
+        // Oracle jumps above the ask: the peg would cross, so it is held one tick
+        // below the best ask rather than locking the book.
+        book.update_oracle_price(Decimal::from(150));
+        assert_eq!(book.get_best_bid(), Some(Decimal::from(99)));
+        assert_eq!(book.get_best_ask(), Some(Decimal::from(100)));
+    }
+
+    #[test]
+    fn pegged_order_is_validated_against_market_params() {
+        let params = MarketParams {
+            tick_size: Decimal::from(5),
+            lot_size: Decimal::from(2),
+            min_size: Decimal::from(4),
+        };
+        let mut book = OrderBook::with_market_params(params);
+        book.update_oracle_price(Decimal::from(100));
+
+        // Amount below the minimum size is rejected before entering the book.
+        let dust = order(Side::Buy, OrderType::PegOffset, None, 3, Uuid::new_v4());
+        assert!(matches!(
+            book.add_pegged_order(dust, Decimal::ZERO, Decimal::from(1_000)),
+            Err(OrderBookError::BelowMinSize { .. })
+        ));
+
+        // A compliant peg whose raw price is off-grid snaps down onto the tick.
+        let bid = order(Side::Buy, OrderType::PegOffset, None, 4, Uuid::new_v4());
+        book.add_pegged_order(bid, Decimal::from(1), Decimal::from(1_000))
+            .unwrap();
+        assert_eq!(book.get_best_bid(), Some(Decimal::from(100)));
+    }
+
+    #[test]
+    fn stp_cancel_taker_leaves_maker_resting() {
+        let user = Uuid::new_v4();
+        let mut book = OrderBook::new();
+        book.set_stp_mode(StpMode::CancelTaker);
+        let maker = order(Side::Sell, OrderType::Limit, Some(100), 5, user);
+        let maker_id = maker.id;
+        book.add_ask(maker).unwrap();
+
+        let result = book.match_order(order(Side::Buy, OrderType::Limit, Some(100), 5, user));
+
+        assert!(result.trades.is_empty());
+        assert!(result.stp_cancelled_maker_orders.is_empty());
+        assert!(result.remaining_taker_order.is_none());
+        assert!(book.get_order_by_id(&maker_id).is_some());
+    }
+
+    #[test]
+    fn stp_cancel_maker_removes_maker_and_lets_taker_continue() {
+        let user = Uuid::new_v4();
+        let mut book = OrderBook::new();
+        book.set_stp_mode(StpMode::CancelMaker);
+        let own_maker = order(Side::Sell, OrderType::Limit, Some(100), 5, user);
+        let own_maker_id = own_maker.id;
+        book.add_ask(own_maker).unwrap();
+        let other_maker = order(Side::Sell, OrderType::Limit, Some(101), 5, Uuid::new_v4());
+        book.add_ask(other_maker).unwrap();
+
+        let result = book.match_order(order(Side::Buy, OrderType::Limit, Some(101), 5, user));
+
+        assert_eq!(result.stp_cancelled_maker_orders, vec![own_maker_id]);
+        assert!(book.get_order_by_id(&own_maker_id).is_none());
+        // Taker skipped its own order and filled against the other maker.
+        assert_eq!(result.trades.len(), 1);
+    }
+
+    #[test]
+    fn stp_cancel_both_cancels_maker_and_taker() {
+        let user = Uuid::new_v4();
+        let mut book = OrderBook::new();
+        book.set_stp_mode(StpMode::CancelBoth);
+        let maker = order(Side::Sell, OrderType::Limit, Some(100), 5, user);
+        let maker_id = maker.id;
+        book.add_ask(maker).unwrap();
+
+        let result = book.match_order(order(Side::Buy, OrderType::Limit, Some(100), 5, user));
+
+        assert_eq!(result.stp_cancelled_maker_orders, vec![maker_id]);
+        assert!(result.trades.is_empty());
+        assert!(result.remaining_taker_order.is_none());
+        assert!(book.get_order_by_id(&maker_id).is_none());
+    }
+
+    #[test]
+    fn stp_decrement_and_cancel_marks_cancelled_not_filled() {
+        let user = Uuid::new_v4();
+        let mut book = OrderBook::new();
+        book.set_stp_mode(StpMode::DecrementAndCancel);
+        // Maker larger than taker: the taker is the smaller side and is cancelled.
+        let maker = order(Side::Sell, OrderType::Limit, Some(100), 8, user);
+        let maker_id = maker.id;
+        book.add_ask(maker).unwrap();
+
+        let result = book.match_order(order(Side::Buy, OrderType::Limit, Some(100), 5, user));
+
+        assert!(result.trades.is_empty());
+        assert!(result.remaining_taker_order.is_none());
+        // The fully-decremented taker must be reported cancelled, never filled.
+        assert!(matches!(
+            result.events.last(),
+            Some(BookEvent::Out {
+                reason: OutReason::Cancelled,
+                ..
+            })
+        ));
+        // Maker survives with its amount decremented by the overlap.
+        assert!(book.get_order_by_id(&maker_id).is_some());
+        // The decrement of the surviving maker is recorded as its own event so
+        // the reserved balance can be unwound without a counterparty fill.
+        assert!(result.events.iter().any(|e| matches!(
+            e,
+            BookEvent::StpDecrement {
+                order_id,
+                amount,
+                remaining,
+            } if *order_id == maker_id
+                && *amount == Decimal::from(5)
+                && *remaining == Decimal::from(3)
+        )));
+    }
+
+    #[test]
+    fn ioc_cancels_unfilled_remainder_instead_of_resting() {
+        let mut book = OrderBook::new();
+        book.add_ask(order(Side::Sell, OrderType::Limit, Some(100), 3, Uuid::new_v4()))
+            .unwrap();
+
+        let mut taker = order(Side::Buy, OrderType::Limit, Some(100), 5, Uuid::new_v4());
+        taker.time_in_force = TimeInForce::Ioc;
+        let result = book.match_order(taker);
+
+        assert_eq!(result.trades.len(), 1);
+        // Remainder of 2 is cancelled, not rested.
+        assert!(result.remaining_taker_order.is_none());
+    }
+
+    #[test]
+    fn fok_kills_when_not_fully_fillable() {
+        let mut book = OrderBook::new();
+        book.add_ask(order(Side::Sell, OrderType::Limit, Some(100), 3, Uuid::new_v4()))
+            .unwrap();
+
+        let mut taker = order(Side::Buy, OrderType::Limit, Some(100), 5, Uuid::new_v4());
+        taker.time_in_force = TimeInForce::Fok;
+        let result = book.match_order(taker);
+
+        assert!(result.trades.is_empty());
+        assert!(result.remaining_taker_order.is_none());
+    }
+
+    #[test]
+    fn fok_fills_when_fully_fillable() {
+        let mut book = OrderBook::new();
+        book.add_ask(order(Side::Sell, OrderType::Limit, Some(100), 5, Uuid::new_v4()))
+            .unwrap();
+
+        let mut taker = order(Side::Buy, OrderType::Limit, Some(100), 5, Uuid::new_v4());
+        taker.time_in_force = TimeInForce::Fok;
+        let result = book.match_order(taker);
+
+        assert_eq!(result.trades.len(), 1);
+        assert!(result.remaining_taker_order.is_none());
+    }
+
+    #[test]
+    fn fok_honors_slippage_guard_in_fillability() {
+        let mut book = OrderBook::new();
+        book.add_ask(order(Side::Sell, OrderType::Limit, Some(100), 1, Uuid::new_v4()))
+            .unwrap();
+        book.add_ask(order(Side::Sell, OrderType::Limit, Some(200), 1, Uuid::new_v4()))
+            .unwrap();
+
+        // Needs 2, but the 200 level lies beyond a 1000 bps guard off a 100
+        // reference, so only 1 is reachable: the order must be killed whole
+        // rather than partially filling against the first level.
+        let mut taker = order(Side::Buy, OrderType::Market, None, 2, Uuid::new_v4());
+        taker.time_in_force = TimeInForce::Fok;
+        taker.max_slippage_bps = Some(Decimal::from(1000));
+        let result = book.match_order_with_guard(taker, Decimal::from(100));
+
+        assert!(result.trades.is_empty());
+        assert!(result.remaining_taker_order.is_none());
+        // Both levels remain untouched.
+        assert_eq!(book.get_best_ask(), Some(Decimal::from(100)));
+    }
+
+    #[test]
+    fn gtd_eviction_is_capped_per_match() {
+        let mut book = OrderBook::new();
+        // Seven expired asks, each on its own level (taker timestamp is 0).
+        for i in 0..7 {
+            let mut ask = order(Side::Sell, OrderType::Limit, Some(100 + i), 5, Uuid::new_v4());
+            ask.expiry_timestamp = Some(-1);
+            book.add_ask(ask).unwrap();
+        }
+
+        let taker = order(Side::Buy, OrderType::Limit, Some(200), 1, Uuid::new_v4());
+        let result = book.match_order(taker);
+
+        assert!(result.trades.is_empty());
+        assert_eq!(result.expired_maker_orders.len(), MAX_EXPIRED_PER_MATCH);
+    }
+
+    #[test]
+    fn market_params_reject_sub_tick_dust_and_odd_lots() {
+        let params = MarketParams {
+            tick_size: Decimal::from(5),
+            lot_size: Decimal::from(2),
+            min_size: Decimal::from(4),
+        };
+        let mut book = OrderBook::with_market_params(params);
+
+        // Price not a multiple of the tick size.
+        assert_eq!(
+            book.add_bid(order(Side::Buy, OrderType::Limit, Some(103), 4, Uuid::new_v4())),
+            Err(OrderBookError::InvalidTickSize {
+                price: Decimal::from(103),
+                tick_size: Decimal::from(5),
+            })
+        );
+        // Amount below the minimum size.
+        assert!(matches!(
+            book.add_bid(order(Side::Buy, OrderType::Limit, Some(100), 2, Uuid::new_v4())),
+            Err(OrderBookError::BelowMinSize { .. })
+        ));
+        // Amount not a multiple of the lot size.
+        assert!(matches!(
+            book.add_bid(order(Side::Buy, OrderType::Limit, Some(100), 5, Uuid::new_v4())),
+            Err(OrderBookError::InvalidLotSize { .. })
+        ));
+        // A compliant order is accepted.
+        assert_eq!(
+            book.add_bid(order(Side::Buy, OrderType::Limit, Some(100), 4, Uuid::new_v4())),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn slippage_guard_stops_before_walking_a_thin_book() {
+        let mut book = OrderBook::new();
+        book.add_ask(order(Side::Sell, OrderType::Limit, Some(100), 1, Uuid::new_v4()))
+            .unwrap();
+        book.add_ask(order(Side::Sell, OrderType::Limit, Some(200), 1, Uuid::new_v4()))
+            .unwrap();
+
+        // Allow at most 10% (1000 bps) deviation from the reference price of 100.
+        let mut taker = order(Side::Buy, OrderType::Market, None, 2, Uuid::new_v4());
+        taker.max_slippage_bps = Some(Decimal::from(1000));
+        let result = book.match_order_with_guard(taker, Decimal::from(100));
+
+        // Only the first level fills; the 200 level breaches the guard.
+        assert_eq!(result.trades.len(), 1);
+        assert_eq!(result.trades[0].price, Decimal::from(100));
+        assert!(result.slippage_guard_triggered);
+        assert!(result.remaining_taker_order.is_none());
+        // The untouched level is still resting.
+        assert_eq!(book.get_best_ask(), Some(Decimal::from(200)));
+    }
+
+    #[test]
+    fn event_stream_reflects_fill_and_outs_in_order() {
+        let mut book = OrderBook::new();
+        let maker = order(Side::Sell, OrderType::Limit, Some(100), 5, Uuid::new_v4());
+        let maker_id = maker.id;
+        book.add_ask(maker).unwrap();
+
+        let taker = order(Side::Buy, OrderType::Limit, Some(100), 5, Uuid::new_v4());
+        let taker_id = taker.id;
+        let result = book.match_order(taker);
+
+        // Fill, then maker fully out, then taker fully out — in that order.
+        assert!(matches!(
+            result.events[0],
+            BookEvent::Fill {
+                maker_id: m,
+                taker_id: t,
+                ..
+            } if m == maker_id && t == taker_id
+        ));
+        assert!(matches!(
+            result.events[1],
+            BookEvent::Out {
+                order_id,
+                reason: OutReason::Filled,
+            } if order_id == maker_id
+        ));
+        assert!(matches!(
+            result.events[2],
+            BookEvent::Out {
+                order_id,
+                reason: OutReason::Filled,
+            } if order_id == taker_id
+        ));
+    }
+}